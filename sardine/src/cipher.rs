@@ -0,0 +1,421 @@
+use aes_frast::{aes_core, aes_with_operation_mode};
+use chacha::{ChaCha, KeyStream};
+
+use srd_errors::SrdError;
+use Result;
+
+/// Negotiated via the `cipher` field of `SrdAccept`.
+pub const SRD_CIPHER_AES_CBC: u32 = 0;
+pub const SRD_CIPHER_XCHACHA20: u32 = 1;
+pub const SRD_CIPHER_AES256_EAX: u32 = 2;
+
+/// A symmetric algorithm selected by the negotiated `cipher` value, abstracted
+/// behind a single block-mode-of-operation style interface so `SrdDelegate`
+/// does not need to know which cipher it is driving.
+///
+/// `encrypt`/`decrypt` return an owned `Vec<u8>` rather than writing into a
+/// caller-sized `dst: &mut [u8]`: `AesCbcCipher` PKCS#7-pads its input, so
+/// the output length isn't known to the caller ahead of time. `iv` is `&[u8]`
+/// rather than `&mut [u8]` because no implementation here needs to thread
+/// state (e.g. the last ciphertext block) back to the caller between calls.
+pub trait SrdCipher {
+    fn encrypt(&mut self, iv: &[u8], src: &[u8]) -> Result<Vec<u8>>;
+    fn decrypt(&mut self, iv: &[u8], src: &[u8]) -> Result<Vec<u8>>;
+}
+
+pub struct AesCbcCipher {
+    enc_keys: Vec<u32>,
+    dec_keys: Vec<u32>,
+}
+
+impl AesCbcCipher {
+    /// Derives an AES-128, AES-192 or AES-256 key schedule from `key`,
+    /// chosen by its length (16/24/32 bytes) as negotiated via `key_size`.
+    pub fn new(key: &[u8]) -> Result<Self> {
+        let w_len = match key.len() {
+            16 => 44,
+            24 => 52,
+            32 => 60,
+            _ => return Err(SrdError::InvalidKeySize),
+        };
+
+        let mut enc_keys = vec![0u32; w_len];
+        let mut dec_keys = vec![0u32; w_len];
+
+        aes_core::setkey_enc_auto(key, &mut enc_keys);
+        aes_core::setkey_dec_auto(key, &mut dec_keys);
+
+        Ok(AesCbcCipher { enc_keys, dec_keys })
+    }
+}
+
+impl SrdCipher for AesCbcCipher {
+    /// PKCS#7-pads `src` up to the block size before encryption, so callers
+    /// may hand in a `SrdBlob` serialization of any length.
+    fn encrypt(&mut self, iv: &[u8], src: &[u8]) -> Result<Vec<u8>> {
+        let padded = pkcs7_pad(src, 16);
+        let mut dst = vec![0u8; padded.len()];
+
+        aes_with_operation_mode::cbc_enc(&padded, &mut dst, &self.enc_keys, &iv[0..16]);
+        Ok(dst)
+    }
+
+    fn decrypt(&mut self, iv: &[u8], src: &[u8]) -> Result<Vec<u8>> {
+        if src.len() % 16 != 0 || src.is_empty() {
+            return Err(SrdError::InvalidDataLength);
+        }
+
+        let mut padded = vec![0u8; src.len()];
+        aes_with_operation_mode::cbc_dec(src, &mut padded, &self.dec_keys, &iv[0..16]);
+
+        pkcs7_unpad(&padded)
+    }
+}
+
+/// Pads `data` to a multiple of `block_size` per PKCS#7 (RFC 5652 §6.3):
+/// appends `p` bytes each equal to `p`, always adding a full block when
+/// `data` is already aligned so the padding can be unambiguously stripped.
+fn pkcs7_pad(data: &[u8], block_size: usize) -> Vec<u8> {
+    let pad_len = block_size - (data.len() % block_size);
+    let mut padded = Vec::with_capacity(data.len() + pad_len);
+    padded.extend_from_slice(data);
+    padded.extend(std::iter::repeat(pad_len as u8).take(pad_len));
+    padded
+}
+
+fn pkcs7_unpad(data: &[u8]) -> Result<Vec<u8>> {
+    let pad_len = *data.last().ok_or(SrdError::InvalidPadding)? as usize;
+    if pad_len == 0 || pad_len > 16 || pad_len > data.len() {
+        return Err(SrdError::InvalidPadding);
+    }
+
+    let unpadded_len = data.len() - pad_len;
+    if !data[unpadded_len..].iter().all(|&b| b as usize == pad_len) {
+        return Err(SrdError::InvalidPadding);
+    }
+
+    Ok(data[..unpadded_len].to_vec())
+}
+
+pub struct XChaCha20Cipher {
+    key: [u8; 32],
+}
+
+impl XChaCha20Cipher {
+    pub fn new(key: &[u8]) -> Self {
+        let mut k = [0u8; 32];
+        k.copy_from_slice(key);
+        XChaCha20Cipher { key: k }
+    }
+}
+
+impl SrdCipher for XChaCha20Cipher {
+    fn encrypt(&mut self, iv: &[u8], src: &[u8]) -> Result<Vec<u8>> {
+        let mut iv_ref = [0u8; 24];
+        iv_ref.copy_from_slice(&iv[0..24]);
+
+        let mut stream = ChaCha::new_xchacha20(&self.key, &iv_ref);
+        let mut dst = src.to_vec();
+        // `xor_read` only fails when the keystream runs out before `dst`
+        // does; `chacha::Error` has no `SrdError` conversion, so map it
+        // explicitly rather than relying on `?`.
+        stream
+            .xor_read(&mut dst)
+            .map_err(|_| SrdError::InvalidDataLength)?;
+        Ok(dst)
+    }
+
+    fn decrypt(&mut self, iv: &[u8], src: &[u8]) -> Result<Vec<u8>> {
+        // XChaCha20 is a stream cipher: encryption and decryption are the same operation.
+        self.encrypt(iv, src)
+    }
+}
+
+/// Looks up the `SrdCipher` implementation negotiated in `SrdAccept::cipher`.
+/// The AES variant is selected by the length of `key` (the derived
+/// delegation key), not by `SrdAccept::key_size` — that field carries the
+/// length of the DH public key / prime (or the X25519 coordinate), which is
+/// a separate quantity.
+pub fn cipher_for(cipher: u32, key: &[u8]) -> Result<Box<SrdCipher>> {
+    match cipher {
+        SRD_CIPHER_AES_CBC => Ok(Box::new(AesCbcCipher::new(key)?)),
+        SRD_CIPHER_XCHACHA20 => Ok(Box::new(XChaCha20Cipher::new(key))),
+        _ => Err(SrdError::InvalidCipher),
+    }
+}
+
+/// An authenticated mode that produces its own integrity tag instead of
+/// relying on a separate `compute_mac` pass, binding confidentiality and
+/// integrity (and, via `ad`, the handshake transcript) together.
+pub trait SrdAeadCipher {
+    fn seal(&mut self, nonce: &[u8], ad: &[u8], data: &[u8]) -> Result<(Vec<u8>, [u8; 16])>;
+    fn open(&mut self, nonce: &[u8], ad: &[u8], data: &[u8], tag: &[u8; 16]) -> Result<Vec<u8>>;
+}
+
+/// Looks up the `SrdAeadCipher` implementation negotiated in
+/// `SrdAccept::cipher`, mirroring `cipher_for` for the AEAD modes that need
+/// their own integrity tag instead of a separate `compute_mac` pass.
+pub fn aead_cipher_for(cipher: u32, key: &[u8]) -> Result<Box<SrdAeadCipher>> {
+    match cipher {
+        SRD_CIPHER_AES256_EAX => Ok(Box::new(Aes256EaxCipher::new(key))),
+        _ => Err(SrdError::InvalidCipher),
+    }
+}
+
+pub struct Aes256EaxCipher {
+    enc_keys: Vec<u32>,
+}
+
+impl Aes256EaxCipher {
+    pub fn new(key: &[u8]) -> Self {
+        let mut enc_keys = vec![0u32; 60];
+        aes_core::setkey_enc_auto(key, &mut enc_keys);
+        Aes256EaxCipher { enc_keys }
+    }
+}
+
+impl SrdAeadCipher for Aes256EaxCipher {
+    fn seal(&mut self, nonce: &[u8], ad: &[u8], data: &[u8]) -> Result<(Vec<u8>, [u8; 16])> {
+        let n_mac = omac(&self.enc_keys, 0, nonce);
+        let ciphertext = ctr_crypt(&self.enc_keys, &n_mac, data);
+
+        let a_mac = omac(&self.enc_keys, 1, ad);
+        let c_mac = omac(&self.enc_keys, 2, &ciphertext);
+
+        let mut tag = [0u8; 16];
+        for i in 0..16 {
+            tag[i] = n_mac[i] ^ a_mac[i] ^ c_mac[i];
+        }
+
+        Ok((ciphertext, tag))
+    }
+
+    fn open(&mut self, nonce: &[u8], ad: &[u8], data: &[u8], tag: &[u8; 16]) -> Result<Vec<u8>> {
+        let n_mac = omac(&self.enc_keys, 0, nonce);
+        let a_mac = omac(&self.enc_keys, 1, ad);
+        let c_mac = omac(&self.enc_keys, 2, data);
+
+        let mut expected = [0u8; 16];
+        for i in 0..16 {
+            expected[i] = n_mac[i] ^ a_mac[i] ^ c_mac[i];
+        }
+
+        if !constant_time_eq(&expected, tag) {
+            return Err(SrdError::InvalidMac);
+        }
+
+        Ok(ctr_crypt(&self.enc_keys, &n_mac, data))
+    }
+}
+
+fn constant_time_eq(a: &[u8; 16], b: &[u8; 16]) -> bool {
+    let mut diff = 0u8;
+    for i in 0..16 {
+        diff |= a[i] ^ b[i];
+    }
+    diff == 0
+}
+
+/// Encrypts a single AES block with the key schedule already negotiated,
+/// reusing `cbc_enc` over a zero IV (equivalent to a bare ECB block encrypt).
+fn aes_encrypt_block(enc_keys: &[u32], block: &[u8; 16]) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    aes_with_operation_mode::cbc_enc(block, &mut out, enc_keys, &[0u8; 16]);
+    out
+}
+
+fn gf_double(block: [u8; 16]) -> [u8; 16] {
+    let msb = block[0] & 0x80;
+    let mut out = [0u8; 16];
+    let mut carry = 0u8;
+    for i in (0..16).rev() {
+        let b = block[i];
+        out[i] = (b << 1) | carry;
+        carry = (b >> 7) & 1;
+    }
+    if msb != 0 {
+        out[15] ^= 0x87;
+    }
+    out
+}
+
+/// OMAC1/CMAC over `data`, as specified in NIST SP 800-38B.
+fn cmac(enc_keys: &[u32], data: &[u8]) -> [u8; 16] {
+    let l = aes_encrypt_block(enc_keys, &[0u8; 16]);
+    let k1 = gf_double(l);
+    let k2 = gf_double(k1);
+
+    let complete = !data.is_empty() && data.len() % 16 == 0;
+    let mut blocks: Vec<[u8; 16]> = data.chunks(16).map(|c| {
+        let mut b = [0u8; 16];
+        b[..c.len()].copy_from_slice(c);
+        b
+    }).collect();
+
+    if blocks.is_empty() {
+        blocks.push([0u8; 16]);
+    }
+
+    let last_index = blocks.len() - 1;
+    let tweak = if complete { k1 } else {
+        let pad_len = data.len() % 16;
+        blocks[last_index][pad_len] = 0x80;
+        k2
+    };
+
+    for i in 0..16 {
+        blocks[last_index][i] ^= tweak[i];
+    }
+
+    let mut mac = [0u8; 16];
+    for block in &blocks {
+        let mut xored = [0u8; 16];
+        for i in 0..16 {
+            xored[i] = mac[i] ^ block[i];
+        }
+        mac = aes_encrypt_block(enc_keys, &xored);
+    }
+    mac
+}
+
+/// EAX's `OMAC_t`: CMAC of `t` encoded as a single leading all-zero block
+/// with its last byte set to `t`, followed by `data`.
+fn omac(enc_keys: &[u32], t: u8, data: &[u8]) -> [u8; 16] {
+    let mut buffer = Vec::with_capacity(16 + data.len());
+    let mut prefix = [0u8; 16];
+    prefix[15] = t;
+    buffer.extend_from_slice(&prefix);
+    buffer.extend_from_slice(data);
+    cmac(enc_keys, &buffer)
+}
+
+/// AES-CTR over `data`, counting up from `iv` as a 128-bit big-endian integer.
+fn ctr_crypt(enc_keys: &[u32], iv: &[u8; 16], data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; data.len()];
+    let mut counter = *iv;
+
+    for (chunk, out_chunk) in data.chunks(16).zip(out.chunks_mut(16)) {
+        let keystream = aes_encrypt_block(enc_keys, &counter);
+        for i in 0..chunk.len() {
+            out_chunk[i] = chunk[i] ^ keystream[i];
+        }
+
+        for byte in counter.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // NIST SP 800-38B, Appendix D.1 (AES-128).
+    const CMAC_KEY: [u8; 16] = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+
+    fn cmac_enc_keys() -> Vec<u32> {
+        let mut enc_keys = vec![0u32; 44];
+        aes_core::setkey_enc_auto(&CMAC_KEY, &mut enc_keys);
+        enc_keys
+    }
+
+    #[test]
+    fn cmac_empty_message_matches_known_answer() {
+        let expected = [
+            0xbb, 0x1d, 0x69, 0x29, 0xe9, 0x59, 0x37, 0x28, 0x7f, 0xa3, 0x7d, 0x12, 0x9b, 0x75,
+            0x67, 0x46,
+        ];
+        assert_eq!(cmac(&cmac_enc_keys(), &[]), expected);
+    }
+
+    #[test]
+    fn cmac_one_block_message_matches_known_answer() {
+        let message = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a,
+        ];
+        let expected = [
+            0x07, 0x0a, 0x16, 0xb4, 0x6b, 0x4d, 0x41, 0x44, 0xf7, 0x9b, 0xdd, 0x9d, 0xd0, 0x4a,
+            0x28, 0x7c,
+        ];
+        assert_eq!(cmac(&cmac_enc_keys(), &message), expected);
+    }
+
+    #[test]
+    fn pkcs7_round_trips_for_various_lengths() {
+        for len in &[0usize, 1, 15, 16, 17, 31, 32] {
+            let data: Vec<u8> = (0..*len as u8).collect();
+            let padded = pkcs7_pad(&data, 16);
+            assert_eq!(padded.len() % 16, 0);
+            assert!(!padded.is_empty());
+            assert_eq!(pkcs7_unpad(&padded).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn pkcs7_pad_appends_a_full_block_when_already_aligned() {
+        let data = [0u8; 16];
+        let padded = pkcs7_pad(&data, 16);
+        assert_eq!(padded.len(), 32);
+        assert_eq!(&padded[16..], &[16u8; 16][..]);
+    }
+
+    #[test]
+    fn pkcs7_unpad_rejects_malformed_padding() {
+        assert!(pkcs7_unpad(&[]).is_err());
+        assert!(pkcs7_unpad(&[1, 2, 3, 0]).is_err());
+        assert!(pkcs7_unpad(&[1, 2, 3, 17]).is_err());
+    }
+
+    #[test]
+    fn aes_cbc_round_trips() {
+        let key = [0x42u8; 32];
+        let iv = [0x24u8; 32];
+        let plaintext = b"the quick brown fox jumps".to_vec();
+
+        let mut cipher = AesCbcCipher::new(&key).unwrap();
+        let ciphertext = cipher.encrypt(&iv, &plaintext).unwrap();
+        let recovered = cipher.decrypt(&iv, &ciphertext).unwrap();
+
+        assert_eq!(recovered, plaintext);
+    }
+
+    #[test]
+    fn eax_seal_open_round_trips() {
+        let key = [0x11u8; 32];
+        let nonce = [0x22u8; 16];
+        let ad = b"transcript".to_vec();
+        let plaintext = b"delegated blob".to_vec();
+
+        let mut sealer = Aes256EaxCipher::new(&key);
+        let (ciphertext, tag) = sealer.seal(&nonce, &ad, &plaintext).unwrap();
+
+        let mut opener = Aes256EaxCipher::new(&key);
+        let recovered = opener.open(&nonce, &ad, &ciphertext, &tag).unwrap();
+
+        assert_eq!(recovered, plaintext);
+    }
+
+    #[test]
+    fn eax_open_rejects_tampered_tag() {
+        let key = [0x11u8; 32];
+        let nonce = [0x22u8; 16];
+        let ad = b"transcript".to_vec();
+        let plaintext = b"delegated blob".to_vec();
+
+        let mut sealer = Aes256EaxCipher::new(&key);
+        let (ciphertext, mut tag) = sealer.seal(&nonce, &ad, &plaintext).unwrap();
+        tag[0] ^= 0xff;
+
+        let mut opener = Aes256EaxCipher::new(&key);
+        assert!(opener.open(&nonce, &ad, &ciphertext, &tag).is_err());
+    }
+}
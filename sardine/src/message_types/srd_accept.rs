@@ -4,8 +4,10 @@ use std::io::Read;
 use std::io::Write;
 
 use Result;
-use message_types::{expand_start, SrdMessage, SrdPacket, srd_flags::{SRD_FLAG_CBT, SRD_FLAG_MAC},
+use message_types::{expand_start, SrdMessage, SrdPacket, srd_flags::{SRD_FLAG_CBT, SRD_FLAG_ECDH, SRD_FLAG_MAC},
                     srd_msg_id::SRD_ACCEPT_MSG_ID, SRD_SIGNATURE};
+use srd_errors::SrdError;
+use x25519;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SrdAccept {
@@ -110,12 +112,29 @@ impl SrdAccept {
         seq_num: u8,
         cipher: u32,
         key_size: u16,
+        challenge_key_size: u16,
         mut public_key: Vec<u8>,
         nonce: [u8; 32],
         cbt_opt: Option<[u8; 32]>,
+        ecdh: bool,
         previous_messages: &[Box<SrdPacket>],
         integrity_key: &[u8],
     ) -> Result<Self> {
+        if ecdh && key_size != 32 {
+            // X25519 public keys are always a 32-byte Montgomery-u coordinate.
+            return Err(SrdError::InvalidKeySize);
+        }
+
+        // `key_size` here is the length of the DH public key / prime (or the
+        // X25519 coordinate above), not the AES key length: a classical
+        // finite-field handshake carries `key_size = 256` for a 2048-bit
+        // prime. The AES variant is selected by the length of the derived
+        // delegation key in `cipher_for`, not by this field.
+        if key_size != challenge_key_size {
+            // Both sides of the handshake must agree on the negotiated key size.
+            return Err(SrdError::InvalidKeySize);
+        }
+
         expand_start(&mut public_key, key_size as usize);
         let mut cbt = [0u8; 32];
         let mut flags = SRD_FLAG_MAC;
@@ -128,6 +147,10 @@ impl SrdAccept {
             }
         }
 
+        if ecdh {
+            flags |= SRD_FLAG_ECDH;
+        }
+
         let mut response = SrdAccept {
             signature: SRD_SIGNATURE,
             packet_type: SRD_ACCEPT_MSG_ID,
@@ -149,6 +172,57 @@ impl SrdAccept {
     pub fn has_cbt(&self) -> bool {
         self.flags & SRD_FLAG_CBT != 0
     }
+
+    /// When set, `public_key` is a 32-byte X25519 Montgomery-u coordinate
+    /// negotiated over ECDH rather than a finite-field Diffie-Hellman key.
+    pub fn has_ecdh(&self) -> bool {
+        self.flags & SRD_FLAG_ECDH != 0
+    }
+
+    pub fn key_size(&self) -> u16 {
+        self.key_size
+    }
+
+    /// Builds an `SrdAccept` negotiating X25519 ECDH, generating a fresh
+    /// keypair and returning the private scalar alongside the response so
+    /// the caller can later combine it with the peer's public key via
+    /// `x25519::key_agreement`. `derive_shared_secret` on the returned
+    /// response would pair it with its own `public_key`, not the peer's.
+    pub fn new_x25519(
+        seq_num: u8,
+        cipher: u32,
+        nonce: [u8; 32],
+        cbt_opt: Option<[u8; 32]>,
+        previous_messages: &[Box<SrdPacket>],
+        integrity_key: &[u8],
+    ) -> Result<(Self, [u8; 32])> {
+        let (private_key, public_key) = x25519::generate_keypair()?;
+        let response = SrdAccept::new(
+            seq_num,
+            cipher,
+            32,
+            32,
+            public_key.to_vec(),
+            nonce,
+            cbt_opt,
+            true,
+            previous_messages,
+            integrity_key,
+        )?;
+        Ok((response, private_key))
+    }
+
+    /// Derives the X25519 shared secret from `private_key` and this
+    /// message's negotiated `public_key`. Only meaningful when `has_ecdh()`.
+    pub fn derive_shared_secret(&self, private_key: &[u8; 32]) -> Result<[u8; 32]> {
+        if !self.has_ecdh() || self.public_key.len() != 32 {
+            return Err(SrdError::InvalidKeySize);
+        }
+
+        let mut peer_public_key = [0u8; 32];
+        peer_public_key.copy_from_slice(&self.public_key);
+        Ok(x25519::key_agreement(private_key, &peer_public_key))
+    }
 }
 
 #[cfg(test)]
@@ -162,9 +236,11 @@ mod test {
             2,
             0,
             256,
+            256,
             vec![0u8; 256],
             [0u8; 32],
             Some([0u8; 32]),
+            false,
             &Vec::new(),
             &[0u8; 32],
         ).unwrap();
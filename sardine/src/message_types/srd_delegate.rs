@@ -2,17 +2,11 @@ use std;
 use std::io::{Read, Write};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-#[cfg(not(feature = "chacha20"))]
-use aes_frast::{aes_core, aes_with_operation_mode};
-
-#[cfg(not(feature = "chacha20"))]
-use srd_errors::SrdError;
-
-#[cfg(feature = "chacha20")]
-use chacha::{ChaCha, KeyStream};
-
-use message_types::{SrdMessage, SrdPacket, srd_flags::SRD_FLAG_MAC, srd_msg_id::SRD_DELEGATE_MSG_ID, SRD_SIGNATURE};
+use cipher::{aead_cipher_for, cipher_for, SRD_CIPHER_AES256_EAX};
+use message_types::{SrdMessage, SrdPacket, srd_flags::{SRD_FLAG_AEAD_MAC, SRD_FLAG_MAC}, srd_msg_id::SRD_DELEGATE_MSG_ID,
+                    SRD_SIGNATURE};
 use srd_blob::SrdBlob;
+use srd_errors::SrdError;
 use Result;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -102,10 +96,38 @@ impl SrdDelegate {
         integrity_key: &[u8],
         delegation_key: &[u8],
         iv: &[u8],
+        cipher: u32,
     ) -> Result<Self> {
         let mut v_blob = Vec::new();
         srd_blob.write_to(&mut v_blob)?;
-        let encrypted_blob = encrypt_data(&v_blob, delegation_key, iv)?;
+
+        if cipher == SRD_CIPHER_AES256_EAX {
+            let associated_data = transcript(previous_messages)?;
+            let mut aead = aead_cipher_for(cipher, delegation_key)?;
+            let (encrypted_blob, tag) = aead.seal(iv, &associated_data, &v_blob)?;
+
+            // EAX's tag is only 16 bytes; `mac[16..]` carries no data and is
+            // left zeroed rather than running `compute_mac` over it, since
+            // the tag already authenticates `associated_data` and the blob.
+            // `SRD_FLAG_AEAD_MAC` (not `SRD_FLAG_MAC`) marks `mac` as an AEAD
+            // tag rather than a `compute_mac` transcript digest, so generic
+            // per-packet MAC verification does not run `compute_mac` against
+            // it: `get_data` authenticates this packet itself via `open`.
+            let mut mac = [0u8; 32];
+            mac[..16].copy_from_slice(&tag);
+
+            return Ok(SrdDelegate {
+                signature: SRD_SIGNATURE,
+                packet_type: SRD_DELEGATE_MSG_ID,
+                seq_num,
+                flags: SRD_FLAG_AEAD_MAC,
+                size: (encrypted_blob.len() as u32),
+                encrypted_blob,
+                mac,
+            });
+        }
+
+        let encrypted_blob = encrypt_data(&v_blob, delegation_key, iv, cipher)?;
 
         let mut response = SrdDelegate {
             signature: SRD_SIGNATURE,
@@ -121,8 +143,34 @@ impl SrdDelegate {
         Ok(response)
     }
 
-    pub fn get_data(&self, key: &[u8], iv: &[u8]) -> Result<SrdBlob> {
-        let buffer = decrypt_data(&self.encrypted_blob, key, iv)?;
+    /// Decrypts and, for `SRD_CIPHER_AES256_EAX`, authenticates this
+    /// delegate's blob. EAX delegates set `SRD_FLAG_AEAD_MAC` instead of
+    /// `SRD_FLAG_MAC`: their authentication is the AEAD `open` call below,
+    /// not a `compute_mac` check against `mac`, so callers must not also
+    /// run `compute_mac` verification against `mac` for this packet.
+    pub fn get_data(
+        &self,
+        key: &[u8],
+        iv: &[u8],
+        cipher: u32,
+        previous_messages: &[Box<SrdPacket>],
+    ) -> Result<SrdBlob> {
+        let buffer = if cipher == SRD_CIPHER_AES256_EAX {
+            if self.mac[16..].iter().any(|&b| b != 0) {
+                // The upper half of `mac` carries no data for EAX; a peer
+                // setting it means the packet was not built by `SrdDelegate::new`.
+                return Err(SrdError::InvalidMac);
+            }
+
+            let associated_data = transcript(previous_messages)?;
+            let mut tag = [0u8; 16];
+            tag.copy_from_slice(&self.mac[..16]);
+
+            let mut aead = aead_cipher_for(cipher, key)?;
+            aead.open(iv, &associated_data, &self.encrypted_blob, &tag)?
+        } else {
+            decrypt_data(&self.encrypted_blob, key, iv, cipher)?
+        };
 
         let mut cursor = std::io::Cursor::new(buffer.as_slice());
         let srd_blob = SrdBlob::read_from(&mut cursor)?;
@@ -130,55 +178,30 @@ impl SrdDelegate {
     }
 }
 
-#[cfg(not(feature = "chacha20"))]
-fn encrypt_data(data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
-    if data.len() % 16 != 0 {
-        return Err(SrdError::InvalidDataLength);
+/// Serializes the prior handshake messages to use as EAX associated data,
+/// binding the delegated blob to the transcript it was negotiated under.
+fn transcript(previous_messages: &[Box<SrdPacket>]) -> Result<Vec<u8>> {
+    let mut associated_data = Vec::new();
+    for message in previous_messages {
+        message.write_to(&mut associated_data)?;
     }
-
-    let mut w_keys = vec![0u32; 60];
-    let mut cipher = vec![0u8; data.len()];
-
-    aes_core::setkey_enc_auto(&key, &mut w_keys);
-    aes_with_operation_mode::cbc_enc(&data, &mut cipher, &w_keys, &iv[0..16]);
-
-    Ok(cipher)
+    Ok(associated_data)
 }
 
-#[cfg(not(feature = "chacha20"))]
-pub fn decrypt_data(data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
-    if data.len() % 16 != 0 {
-        return Err(SrdError::InvalidDataLength);
-    }
-
-    let mut w_keys = vec![0u32; 60];
-    let mut cipher = vec![0u8; data.len()];
-
-    aes_core::setkey_dec_auto(&key, &mut w_keys);
-    aes_with_operation_mode::cbc_dec(&data, &mut cipher, &w_keys, &iv[0..16]);
+fn encrypt_data(data: &[u8], key: &[u8], iv: &[u8], cipher: u32) -> Result<Vec<u8>> {
+    let mut mode = cipher_for(cipher, key)?;
+    let mut iv_buf = [0u8; 32];
+    iv_buf[..iv.len()].copy_from_slice(iv);
 
-    Ok(cipher)
+    mode.encrypt(&iv_buf, data)
 }
 
-#[cfg(feature = "chacha20")]
-fn encrypt_data(data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
-    let mut key_ref = [0u8; 32];
-    key_ref.copy_from_slice(key);
-
-    let mut iv_ref = [0u8; 24];
-    iv_ref.copy_from_slice(&iv[0..24]);
-
-    let mut stream = ChaCha::new_xchacha20(&key_ref, &iv_ref);
-    let mut buffer = data.to_vec();
-
-    stream.xor_read(&mut buffer)?;
-    Ok(buffer)
-}
+pub fn decrypt_data(data: &[u8], key: &[u8], iv: &[u8], cipher: u32) -> Result<Vec<u8>> {
+    let mut mode = cipher_for(cipher, key)?;
+    let mut iv_buf = [0u8; 32];
+    iv_buf[..iv.len()].copy_from_slice(iv);
 
-#[cfg(feature = "chacha20")]
-fn decrypt_data(data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
-    // As a stream cipher, encryption and decryption works the same:
-    encrypt_data(data, key, iv)
+    mode.decrypt(&iv_buf, data)
 }
 
 //#[cfg(test)]
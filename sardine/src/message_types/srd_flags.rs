@@ -0,0 +1,17 @@
+/// `mac` is a `compute_mac` HMAC-style digest over the handshake transcript,
+/// verified the same way for every packet that sets it.
+pub const SRD_FLAG_MAC: u16 = 0x0001;
+pub const SRD_FLAG_CBT: u16 = 0x0002;
+
+/// Mirrors `now_auth_srd::NOW_AUTH_SRD_FLAG_ECDH` bit-for-bit: both crates
+/// speak the same handshake, so a peer that understands one flag layout
+/// must understand the other.
+pub const SRD_FLAG_ECDH: u16 = 0x0004;
+
+/// `mac` holds an AEAD tag (e.g. EAX's 16-byte tag, zero-padded to 32 bytes)
+/// instead of a `compute_mac` transcript digest. A packet setting this
+/// instead of `SRD_FLAG_MAC` authenticates itself when its payload is
+/// decrypted (see `SrdDelegate::get_data`), so generic per-packet MAC
+/// verification must skip `mac` comparison for it rather than running
+/// `compute_mac`.
+pub const SRD_FLAG_AEAD_MAC: u16 = 0x0008;
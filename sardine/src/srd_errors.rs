@@ -0,0 +1,48 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum SrdError {
+    Io(io::Error),
+    InvalidDataLength,
+    InvalidPadding,
+    InvalidCipher,
+    InvalidKeySize,
+    InvalidMac,
+    RngUnavailable,
+}
+
+impl fmt::Display for SrdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SrdError::Io(ref e) => write!(f, "I/O error: {}", e),
+            SrdError::InvalidDataLength => write!(f, "invalid data length"),
+            SrdError::InvalidPadding => write!(f, "invalid PKCS#7 padding"),
+            SrdError::InvalidCipher => write!(f, "unsupported cipher"),
+            SrdError::InvalidKeySize => write!(f, "invalid key size"),
+            SrdError::InvalidMac => write!(f, "MAC verification failed"),
+            SrdError::RngUnavailable => write!(f, "the OS RNG is unavailable"),
+        }
+    }
+}
+
+impl error::Error for SrdError {
+    fn description(&self) -> &str {
+        match *self {
+            SrdError::Io(_) => "I/O error",
+            SrdError::InvalidDataLength => "invalid data length",
+            SrdError::InvalidPadding => "invalid PKCS#7 padding",
+            SrdError::InvalidCipher => "unsupported cipher",
+            SrdError::InvalidKeySize => "invalid key size",
+            SrdError::InvalidMac => "MAC verification failed",
+            SrdError::RngUnavailable => "the OS RNG is unavailable",
+        }
+    }
+}
+
+impl From<io::Error> for SrdError {
+    fn from(e: io::Error) -> Self {
+        SrdError::Io(e)
+    }
+}
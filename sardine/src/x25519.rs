@@ -0,0 +1,28 @@
+use rand::OsRng;
+use x25519_dalek::{diffie_hellman, generate_public, generate_secret};
+
+use srd_errors::SrdError;
+use Result;
+
+/// Generates a freshly clamped X25519 private scalar and its corresponding
+/// Montgomery-u public key, for the mode negotiated via `SrdAccept::has_ecdh`.
+pub fn generate_keypair() -> Result<([u8; 32], [u8; 32])> {
+    let mut rng = OsRng::new().map_err(|_| SrdError::RngUnavailable)?;
+    let mut private_key = generate_secret(&mut rng);
+
+    // RFC 7748 §5 clamping: clear bits 0-2 of the first byte, clear bit 7
+    // and set bit 6 of the last byte.
+    private_key[0] &= 0xf8;
+    private_key[31] &= 0x7f;
+    private_key[31] |= 0x40;
+
+    let public_key = generate_public(&private_key).to_bytes();
+    Ok((private_key, public_key))
+}
+
+/// X25519 scalar multiplication of `private_key` with the peer's
+/// Montgomery-u coordinate, producing the shared secret `SrdDelegate`'s
+/// integrity/delegation keys are derived from.
+pub fn key_agreement(private_key: &[u8; 32], peer_public_key: &[u8; 32]) -> [u8; 32] {
+    diffie_hellman(private_key, peer_public_key)
+}
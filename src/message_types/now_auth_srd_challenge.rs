@@ -2,11 +2,21 @@ use std;
 use std::io::Read;
 use std::io::Write;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rand::OsRng;
+use x25519_dalek::{diffie_hellman, generate_public, generate_secret};
 
 use message_types::NowAuthSrdMessage;
 use message_types::now_auth_srd_id::NOW_AUTH_SRD_CHALLENGE_ID;
+use srd_errors::SrdError;
 use Result;
 
+/// Set in `flags` when `generator`/`prime` are ignored and `public_key` is a
+/// 32-byte X25519 Montgomery-u coordinate instead of a finite-field DH key.
+/// Mirrors `sardine::message_types::srd_flags::SRD_FLAG_ECDH` bit-for-bit:
+/// both crates speak the same handshake, so a peer that understands one
+/// flag layout must understand the other.
+pub const NOW_AUTH_SRD_FLAG_ECDH: u16 = 0x0004;
+
 pub struct NowAuthSrdChallenge {
     pub packet_type: u16,
     pub flags: u16,
@@ -86,4 +96,42 @@ impl NowAuthSrdChallenge {
             nonce,
         }
     }
+
+    /// Builds a challenge negotiating X25519 ECDH in place of finite-field DH:
+    /// `public_key` is the 32-byte Montgomery-u coordinate derived from
+    /// `private_key`, and `generator`/`prime` are unused.
+    pub fn new_x25519(private_key: &[u8; 32], nonce: [u8; 32]) -> NowAuthSrdChallenge {
+        let public_key = generate_public(private_key).to_bytes().to_vec();
+
+        NowAuthSrdChallenge {
+            packet_type: NOW_AUTH_SRD_CHALLENGE_ID,
+            flags: NOW_AUTH_SRD_FLAG_ECDH,
+            key_size: 32,
+            generator: [0u8; 2],
+            prime: vec![0u8; 32],
+            public_key,
+            nonce,
+        }
+    }
+
+    pub fn is_ecdh(&self) -> bool {
+        self.flags & NOW_AUTH_SRD_FLAG_ECDH != 0
+    }
+}
+
+/// Generates an X25519 private scalar and clamps it per RFC 7748 §5: clear
+/// bits 0-2 of the first byte, clear bit 7 and set bit 6 of the last byte.
+pub fn generate_x25519_private_key() -> Result<[u8; 32]> {
+    let mut rng = OsRng::new().map_err(|_| SrdError::RngUnavailable)?;
+    let mut private_key = generate_secret(&mut rng);
+    private_key[0] &= 0xf8;
+    private_key[31] &= 0x7f;
+    private_key[31] |= 0x40;
+    Ok(private_key)
+}
+
+/// X25519 scalar multiplication of `private_key` with the peer's
+/// Montgomery-u coordinate, producing the shared secret.
+pub fn x25519_key_agreement(private_key: &[u8; 32], peer_public_key: &[u8; 32]) -> [u8; 32] {
+    diffie_hellman(private_key, peer_public_key)
 }
@@ -0,0 +1,33 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum SrdError {
+    Io(io::Error),
+    RngUnavailable,
+}
+
+impl fmt::Display for SrdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SrdError::Io(ref e) => write!(f, "I/O error: {}", e),
+            SrdError::RngUnavailable => write!(f, "the OS RNG is unavailable"),
+        }
+    }
+}
+
+impl error::Error for SrdError {
+    fn description(&self) -> &str {
+        match *self {
+            SrdError::Io(_) => "I/O error",
+            SrdError::RngUnavailable => "the OS RNG is unavailable",
+        }
+    }
+}
+
+impl From<io::Error> for SrdError {
+    fn from(e: io::Error) -> Self {
+        SrdError::Io(e)
+    }
+}